@@ -0,0 +1,107 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+use ffi::*;
+use libc::c_void;
+use types::*;
+
+/// An incrementally-read/written BLOB value, opened against a single row and
+/// column with `sqlite3_blob_open`.
+///
+/// Unlike `Cursor.get_blob()`, a `Blob` never materializes its contents in
+/// full; `read_at`/`write_at` transfer one chunk at a time directly to/from
+/// the database file. The blob's size is fixed at the time it is opened --
+/// `write_at` can overwrite existing bytes but cannot grow the value -- so
+/// resizing a column still has to go through an `UPDATE` statement.
+pub struct Blob<'db> {
+    blob: *mut blob,
+    _dbh: &'db *mut dbh, // make this non-`Send`able, like `Cursor`
+}
+
+/// Opens a BLOB for incremental I/O.
+/// See http://www.sqlite.org/c3ref/blob_open.html
+pub fn blob_open<'db>(dbh: &'db *mut dbh, dbname: &str, table: &str, column: &str,
+                      rowid: i64, read_write: bool) -> SqliteResult<Blob<'db>> {
+    let mut blob = 0 as *mut blob;
+    let r = dbname.with_c_str(|_dbname| {
+        table.with_c_str(|_table| {
+            column.with_c_str(|_column| unsafe {
+                sqlite3_blob_open(*dbh, _dbname, _table, _column, rowid, read_write as int as i32, &mut blob)
+            })
+        })
+    });
+    if r == SQLITE_OK {
+        Ok(Blob { blob: blob, _dbh: dbh })
+    } else {
+        Err(r)
+    }
+}
+
+#[unsafe_destructor]
+impl<'db> Drop for Blob<'db> {
+    /// Closes the BLOB handle.
+    /// See http://www.sqlite.org/c3ref/blob_close.html
+    fn drop(&mut self) {
+        debug!("`Blob.drop()`: blob={}", self.blob);
+        unsafe {
+            sqlite3_blob_close(self.blob);
+        }
+    }
+}
+
+impl<'db> Blob<'db> {
+
+    /// Returns the size in bytes of the BLOB, fixed at the time it was
+    /// opened.
+    /// See http://www.sqlite.org/c3ref/blob_bytes.html
+    pub fn len(&self) -> uint {
+        unsafe {
+            sqlite3_blob_bytes(self.blob) as uint
+        }
+    }
+
+    /// Reads `buf.len()` bytes starting at `offset` into `buf`.
+    /// See http://www.sqlite.org/c3ref/blob_read.html
+    pub fn read_at(&mut self, offset: uint, buf: &mut [u8]) -> ResultCode {
+        unsafe {
+            sqlite3_blob_read(self.blob, buf.as_mut_ptr() as *mut c_void, buf.len() as i32, offset as i32)
+        }
+    }
+
+    /// Writes `buf` starting at `offset`. `offset + buf.len()` must not
+    /// exceed `self.len()`; a BLOB cannot be grown by writing past its end.
+    /// See http://www.sqlite.org/c3ref/blob_write.html
+    pub fn write_at(&mut self, offset: uint, buf: &[u8]) -> ResultCode {
+        unsafe {
+            sqlite3_blob_write(self.blob, buf.as_ptr() as *const c_void, buf.len() as i32, offset as i32)
+        }
+    }
+}
@@ -30,11 +30,13 @@
 */
 
 use ffi::*;
-use libc::{c_int, c_void, c_char};
+use libc::{c_int, c_void, c_char, c_uchar};
 use std::collections::HashMap;
+use std::mem;
 use std::mem::transmute;
 use std::str;
 use std::slice;
+use std::sync::{Mutex, Condvar};
 use std::c_str::CString;
 use types::*;
 
@@ -49,6 +51,38 @@ pub struct Cursor<'db> {
     _dbh: &'db *mut dbh // make this non-`Send`able
 }
 
+/// Converts a possibly-null, NUL-terminated C string into an `Option<&str>`,
+/// with the same lifetime-extending `transmute` used by `get_column_name`
+/// and `get_text`.
+unsafe fn cstr_to_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        None
+    } else {
+        let s = CString::new(ptr, false);
+        let as_str: &str = str::raw::from_utf8(s.as_bytes_no_nul());
+        Some(transmute(as_str)) // make it outlive the original `CString`
+    }
+}
+
+/// The `notify_arg` passed to `sqlite3_unlock_notify`: a condvar the calling
+/// task parks on until the blocking connection releases its lock.
+struct UnlockState {
+    fired: Mutex<bool>,
+    condvar: Condvar,
+}
+
+extern "C" fn unlock_notify_cb(ap_arg: *mut *mut c_void, n_arg: c_int) {
+    unsafe {
+        let mut i = 0i;
+        while i < n_arg as int {
+            let state: &UnlockState = mem::transmute(*ap_arg.offset(i));
+            *state.fired.lock() = true;
+            state.condvar.notify_all();
+            i += 1;
+        }
+    }
+}
+
 pub fn cursor_with_statement<'db>(stmt: *mut stmt, dbh: &'db *mut dbh) -> Cursor<'db> {
     debug!("`Cursor.cursor_with_statement()`: stmt={}", stmt);
     Cursor { stmt: stmt, _dbh: dbh }
@@ -92,6 +126,40 @@ impl<'db> Cursor<'db> {
         }
     }
 
+    /// Like `step()`, but if SQLite is running in shared-cache mode and this
+    /// statement would block on another connection's lock
+    /// (`SQLITE_LOCKED_SHAREDCACHE`), parks the current thread on
+    /// `sqlite3_unlock_notify` and retries instead of returning the error.
+    /// If `unlock_notify` itself reports `SQLITE_LOCKED`, that means SQLite
+    /// detected a deadlock; that is surfaced as an error rather than
+    /// blocking forever.
+    /// See http://www.sqlite.org/unlock_notify.html
+    pub fn step_blocking(&mut self) -> ResultCode {
+        loop {
+            let r = self.step();
+            if r != SQLITE_LOCKED_SHAREDCACHE {
+                return r;
+            }
+
+            let state = UnlockState { fired: Mutex::new(false), condvar: Condvar::new() };
+            let rc = unsafe {
+                sqlite3_unlock_notify(*self._dbh, unlock_notify_cb, mem::transmute(&state))
+            };
+            if rc == SQLITE_LOCKED {
+                // SQLite detected a deadlock involving this connection.
+                return SQLITE_LOCKED;
+            }
+
+            let mut fired = state.fired.lock();
+            while !*fired {
+                fired = state.condvar.wait(fired);
+            }
+            drop(fired);
+
+            self.reset();
+        }
+    }
+
     ///
     pub fn step_row(&mut self) -> SqliteResult<Option<RowMap>> {
         let is_row: ResultCode = self.step();
@@ -187,6 +255,46 @@ impl<'db> Cursor<'db> {
         }
     }
 
+    /// Binds each `(name, value)` pair by resolving `name` (a `:name`,
+    /// `@name` or `$name` marker) to its parameter index with
+    /// `sqlite3_bind_parameter_index`, then binding as `bind_param` would.
+    /// Returns `SQLITE_MISUSE` if any name does not resolve to a parameter
+    /// in this statement.
+    ///
+    /// Unsafe for the same reason as `bind_param`: if any value is a
+    /// `TextBorrowed`/`BlobBorrowed`, the caller must keep its buffer alive
+    /// and unchanged until that parameter is rebound, `clear_bindings` is
+    /// called, or the cursor is dropped.
+    /// See http://www.sqlite.org/c3ref/bind_parameter_index.html
+    pub unsafe fn bind_params_named<'v>(&mut self, values: &[(&str, BindArg<'v>)]) -> ResultCode {
+        for &(name, ref v) in values.iter() {
+            let i = self.get_bind_index(name);
+            if i == 0 {
+                return SQLITE_MISUSE;
+            }
+            let r = self.bind_param(i, v);
+            if r != SQLITE_OK {
+                return r;
+            }
+        }
+        return SQLITE_OK;
+    }
+
+    /// Returns the number of SQL parameters in this statement.
+    /// See http://www.sqlite.org/c3ref/bind_parameter_count.html
+    pub fn get_bind_parameter_count(&self) -> int {
+        unsafe {
+            sqlite3_bind_parameter_count(self.stmt) as int
+        }
+    }
+
+    /// Returns the name of the SQL parameter at index `i` (starting from 1),
+    /// or `None` if it is a nameless `?` parameter or `i` is out of range.
+    /// See http://www.sqlite.org/c3ref/bind_parameter_name.html
+    pub fn get_bind_parameter_name<'a>(&'a self, i: int) -> Option<&'a str> {
+        unsafe { cstr_to_str(sqlite3_bind_parameter_name(self.stmt, i as c_int)) }
+    }
+
     /// Returns the number of columns in a result set.
     /// See http://www.sqlite.org/c3ref/data_count.html
     pub fn get_column_count(&self) -> int {
@@ -208,19 +316,43 @@ impl<'db> Cursor<'db> {
     /// Returns the type of the column with index `i` in the result set.
     /// See http://www.sqlite.org/c3ref/column_blob.html
     pub fn get_column_type(&self, i: int) -> ColumnType {
-        let ct;
-        unsafe {
-            ct = sqlite3_column_type(self.stmt, i as c_int) as int;
-        }
-        let res = match ct {
-            1 /* SQLITE_INTEGER */ => SQLITE_INTEGER,
-            2 /* SQLITE_FLOAT   */ => SQLITE_FLOAT,
-            3 /* SQLITE_TEXT    */ => SQLITE_TEXT,
-            4 /* SQLITE_BLOB    */ => SQLITE_BLOB,
-            5 /* SQLITE_NULL    */ => SQLITE_NULL,
-            _ => panic!(format!("sqlite internal error: Got an unknown column type ({:d}) back from the library.", ct)),
+        let ct = unsafe {
+            sqlite3_column_type(self.stmt, i as c_int) as int
         };
-        return res;
+        column_type_from_raw(ct)
+    }
+
+    /// Returns the declared type of the column with index `i`, i.e. the
+    /// type name given in the `CREATE TABLE` statement, as opposed to the
+    /// runtime storage class `get_column_type()` reports. `None` if the
+    /// result column is not a table column (e.g. it is an expression) or
+    /// has no declared type.
+    /// See http://www.sqlite.org/c3ref/column_decltype.html
+    pub fn get_column_decltype<'a>(&'a self, i: int) -> Option<&'a str> {
+        unsafe { cstr_to_str(sqlite3_column_decltype(self.stmt, i as c_int)) }
+    }
+
+    /// Returns the name of the table that is the origin of the column with
+    /// index `i`, or `None` if the result column is not a table column.
+    /// See http://www.sqlite.org/c3ref/column_database_name.html
+    pub fn get_column_table_name<'a>(&'a self, i: int) -> Option<&'a str> {
+        unsafe { cstr_to_str(sqlite3_column_table_name(self.stmt, i as c_int)) }
+    }
+
+    /// Returns the name of the table column that is the origin of the
+    /// column with index `i`, or `None` if the result column is not a table
+    /// column.
+    /// See http://www.sqlite.org/c3ref/column_database_name.html
+    pub fn get_column_origin_name<'a>(&'a self, i: int) -> Option<&'a str> {
+        unsafe { cstr_to_str(sqlite3_column_origin_name(self.stmt, i as c_int)) }
+    }
+
+    /// Returns the name of the database that is the origin of the column
+    /// with index `i`, or `None` if the result column is not a table
+    /// column.
+    /// See http://www.sqlite.org/c3ref/column_database_name.html
+    pub fn get_column_database_name<'a>(&'a self, i: int) -> Option<&'a str> {
+        unsafe { cstr_to_str(sqlite3_column_database_name(self.stmt, i as c_int)) }
     }
 
     /// Returns the names of all columns in the result set.
@@ -235,8 +367,11 @@ impl<'db> Cursor<'db> {
         return r;
     }
 
-    ///
-    pub fn bind_params(&mut self, values: &[BindArg]) -> ResultCode {
+    /// Unsafe for the same reason as `bind_param`: if any value is a
+    /// `TextBorrowed`/`BlobBorrowed`, the caller must keep its buffer alive
+    /// and unchanged until that parameter is rebound, `clear_bindings` is
+    /// called, or the cursor is dropped.
+    pub unsafe fn bind_params<'v>(&mut self, values: &[BindArg<'v>]) -> ResultCode {
         // SQL parameter index (starting from 1).
         let mut i = 1i;
         for v in values.iter() {
@@ -249,9 +384,16 @@ impl<'db> Cursor<'db> {
         return SQLITE_OK;
     }
 
-    ///
+    /// `TextBorrowed`/`BlobBorrowed` bind with `SQLITE_STATIC`, so SQLite
+    /// reads straight out of `value`'s buffer on every subsequent `step()`
+    /// rather than copying it now. The caller must ensure that buffer stays
+    /// alive and unchanged until the parameter is rebound, `clear_bindings`
+    /// is called, or the cursor is dropped -- `reset` and `step` keep the
+    /// binding in place. Nothing in the signature enforces `'v` against the
+    /// statement's lifetime, so violating this is how you get SQLite reading
+    /// freed memory.
     /// See http://www.sqlite.org/c3ref/bind_blob.html
-    pub fn bind_param(&mut self, i: int, value: &BindArg) -> ResultCode {
+    pub unsafe fn bind_param<'v>(&mut self, i: int, value: &BindArg<'v>) -> ResultCode {
 
         debug!("`Cursor.bind_param()`: stmt={}", self.stmt);
 
@@ -294,6 +436,22 @@ impl<'db> Cursor<'db> {
                 }
             }
 
+            TextBorrowed(v) => {
+                let l = v.len();
+                debug!("  `TextBorrowed`: v={}, l={}", v, l);
+
+                unsafe {
+                    sqlite3_bind_text64(
+                          self.stmt           // the SQL statement
+                        , i as c_int          // the SQL parameter index (starting from 1)
+                        , v.as_ptr() as *const c_char // the value to bind, not copied
+                        , l as u64            // the number of bytes
+                        , 0 as *mut c_void    // SQLITE_STATIC => no copy
+                        , SQLITE_UTF8 as c_uchar
+                        )
+                }
+            }
+
             Blob(ref v) => {
                 let l = v.len();
                 debug!("`Blob`: v={}, l={}", v, l);
@@ -310,6 +468,21 @@ impl<'db> Cursor<'db> {
                 }
             }
 
+            BlobBorrowed(v) => {
+                let l = v.len();
+                debug!("`BlobBorrowed`: v={}, l={}", v, l);
+
+                unsafe {
+                    sqlite3_bind_blob64(
+                          self.stmt         // the SQL statement
+                        , i as c_int       // the SQL parameter index (starting from 1)
+                        , v.as_ptr() as *const c_void // the value to bind, not copied
+                        , l as u64         // the number of bytes
+                        , 0 as *mut c_void // SQLITE_STATIC => no copy
+                        )
+                }
+            }
+
             Integer(ref v) => { unsafe { sqlite3_bind_int(self.stmt, i as c_int, *v as c_int) } }
 
             Integer64(ref v) => { unsafe { sqlite3_bind_int64(self.stmt, i as c_int, *v) } }
@@ -323,3 +496,24 @@ impl<'db> Cursor<'db> {
         return r;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::cstr_to_str;
+    use std::ptr;
+    use libc::c_char;
+
+    #[test]
+    fn cstr_to_str_of_null_is_none() {
+        unsafe {
+            assert!(cstr_to_str(ptr::null::<c_char>()).is_none());
+        }
+    }
+
+    #[test]
+    fn cstr_to_str_of_c_string_round_trips() {
+        "hello".with_c_str(|p| unsafe {
+            assert_eq!(cstr_to_str(p), Some("hello"));
+        });
+    }
+}
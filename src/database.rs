@@ -0,0 +1,111 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+use ffi::*;
+use libc::c_char;
+use std::c_str::CString;
+use std::mem;
+use std::str;
+use cursor::{Cursor, cursor_with_statement};
+use blob::{Blob, blob_open};
+use function::{create_scalar_function, create_aggregate_function};
+use vtab::{VTab, create_module};
+use types::*;
+
+/// The database connection.
+///
+/// `Database` owns the underlying `sqlite3 *` handle and is dropped only
+/// once every `Cursor` borrowed from it has gone out of scope.
+pub struct Database {
+    db: *mut dbh,
+}
+
+impl Database {
+
+    /// Compiles `sql` into a `Cursor` bound to this connection.
+    /// See http://www.sqlite.org/c3ref/prepare.html
+    pub fn prepare<'db>(&'db self, sql: &str) -> SqliteResult<Cursor<'db>> {
+        let mut stmt = 0 as *mut stmt;
+        let r = sql.with_c_str(|_sql| unsafe {
+            sqlite3_prepare_v2(self.db, _sql, -1, &mut stmt, 0 as *mut *const c_char)
+        });
+        if r == SQLITE_OK {
+            Ok(cursor_with_statement(stmt, unsafe { mem::transmute(&self.db) }))
+        } else {
+            Err(r)
+        }
+    }
+
+    /// Opens a single column of a single row for incremental BLOB I/O,
+    /// without loading the value into memory.
+    /// See http://www.sqlite.org/c3ref/blob_open.html
+    pub fn open_blob<'db>(&'db self, dbname: &str, table: &str, column: &str,
+                          rowid: i64, read_write: bool) -> SqliteResult<Blob<'db>> {
+        blob_open(unsafe { mem::transmute(&self.db) }, dbname, table, column, rowid, read_write)
+    }
+
+    /// Registers `f` as a scalar SQL function callable from queries run on
+    /// this connection as `name(...)`.
+    /// See http://www.sqlite.org/c3ref/create_function.html
+    pub fn create_scalar_function(&self, name: &str, n_args: int,
+                                  f: Box<FnMut(&[BindArg<'static>]) -> BindArg<'static> + 'static>) -> ResultCode {
+        create_scalar_function(self.db, name, n_args, f)
+    }
+
+    /// Registers an aggregate SQL function callable from queries run on this
+    /// connection as `name(...)`, backed by an `init`/`step`/`finalize`
+    /// triple and an accumulator of type `A`.
+    /// See http://www.sqlite.org/c3ref/create_function.html
+    pub fn create_aggregate_function<A>(&self, name: &str, n_args: int,
+                                        init: Box<Fn() -> A + 'static>,
+                                        step: Box<FnMut(&mut A, &[BindArg<'static>]) + 'static>,
+                                        finalize: Box<FnMut(A) -> BindArg<'static> + 'static>) -> ResultCode {
+        create_aggregate_function(self.db, name, n_args, init, step, finalize)
+    }
+
+    /// Registers `T` as a virtual table module named `name`, usable in
+    /// `CREATE VIRTUAL TABLE ... USING name(...)` statements on this
+    /// connection.
+    /// See http://www.sqlite.org/c3ref/create_module.html
+    pub fn create_module<T: VTab>(&self, name: &str) -> ResultCode {
+        create_module::<T>(self.db, name)
+    }
+
+    /// Returns the English-language text of the most recent error on this
+    /// connection.
+    /// See http://www.sqlite.org/c3ref/errcode.html
+    pub fn get_errmsg(&self) -> String {
+        unsafe {
+            let msg = CString::new(sqlite3_errmsg(self.db), false);
+            str::from_utf8(msg.as_bytes_no_nul()).unwrap().to_string()
+        }
+    }
+}
@@ -0,0 +1,227 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+//! Raw bindings to the parts of the SQLite3 C API this crate wraps.
+
+use libc::{c_int, c_char, c_void, c_double, c_uchar};
+
+/// Opaque handle to an open database connection (`sqlite3 *`).
+pub enum dbh {}
+
+/// Opaque handle to a prepared statement (`sqlite3_stmt *`).
+pub enum stmt {}
+
+/// Opaque handle to an open incremental BLOB I/O handle (`sqlite3_blob *`).
+pub enum blob {}
+
+/// Opaque handle to an argument or result value passed to an
+/// application-defined SQL function (`sqlite3_value *`).
+pub enum sqlite3_value {}
+
+/// Opaque handle to the `sqlite3_context *` passed to an application-defined
+/// SQL function's step/xFunc/final callbacks.
+pub enum sqlite3_context {}
+
+pub type ResultCode = c_int;
+
+pub static SQLITE_OK: ResultCode = 0;
+pub static SQLITE_ERROR: ResultCode = 1;
+pub static SQLITE_MISUSE: ResultCode = 21;
+pub static SQLITE_ROW: ResultCode = 100;
+pub static SQLITE_DONE: ResultCode = 101;
+pub static SQLITE_LOCKED: ResultCode = 6;
+pub static SQLITE_LOCKED_SHAREDCACHE: ResultCode = (SQLITE_LOCKED as int | (1i << 8)) as ResultCode;
+
+/// Text encoding flag for `sqlite3_create_function_v2`.
+/// See http://www.sqlite.org/c3ref/c_any.html
+pub static SQLITE_UTF8: c_int = 1;
+
+extern "C" {
+    pub fn sqlite3_finalize(stmt: *mut stmt) -> ResultCode;
+    pub fn sqlite3_reset(stmt: *mut stmt) -> ResultCode;
+    pub fn sqlite3_clear_bindings(stmt: *mut stmt) -> ResultCode;
+    pub fn sqlite3_step(stmt: *mut stmt) -> ResultCode;
+
+    pub fn sqlite3_data_count(stmt: *mut stmt) -> c_int;
+    pub fn sqlite3_column_name(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_column_type(stmt: *mut stmt, i: c_int) -> c_int;
+    pub fn sqlite3_column_blob(stmt: *mut stmt, i: c_int) -> *const c_void;
+    pub fn sqlite3_column_bytes(stmt: *mut stmt, i: c_int) -> c_int;
+    pub fn sqlite3_column_int(stmt: *mut stmt, i: c_int) -> c_int;
+    pub fn sqlite3_column_int64(stmt: *mut stmt, i: c_int) -> i64;
+    pub fn sqlite3_column_double(stmt: *mut stmt, i: c_int) -> c_double;
+    pub fn sqlite3_column_text(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_column_decltype(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_column_table_name(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_column_origin_name(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_column_database_name(stmt: *mut stmt, i: c_int) -> *const c_char;
+
+    pub fn sqlite3_bind_parameter_index(stmt: *mut stmt, name: *const c_char) -> c_int;
+    pub fn sqlite3_bind_parameter_count(stmt: *mut stmt) -> c_int;
+    pub fn sqlite3_bind_parameter_name(stmt: *mut stmt, i: c_int) -> *const c_char;
+    pub fn sqlite3_bind_text(stmt: *mut stmt, i: c_int, value: *const c_char, n: c_int, destructor: *mut c_void) -> ResultCode;
+    pub fn sqlite3_bind_blob(stmt: *mut stmt, i: c_int, value: *const c_void, n: c_int, destructor: *mut c_void) -> ResultCode;
+    pub fn sqlite3_bind_int(stmt: *mut stmt, i: c_int, value: c_int) -> ResultCode;
+    pub fn sqlite3_bind_int64(stmt: *mut stmt, i: c_int, value: i64) -> ResultCode;
+    pub fn sqlite3_bind_double(stmt: *mut stmt, i: c_int, value: c_double) -> ResultCode;
+    pub fn sqlite3_bind_null(stmt: *mut stmt, i: c_int) -> ResultCode;
+    pub fn sqlite3_bind_text64(stmt: *mut stmt, i: c_int, value: *const c_char, n: u64, destructor: *mut c_void, encoding: c_uchar) -> ResultCode;
+    pub fn sqlite3_bind_blob64(stmt: *mut stmt, i: c_int, value: *const c_void, n: u64, destructor: *mut c_void) -> ResultCode;
+
+    pub fn sqlite3_unlock_notify(
+        db: *mut dbh,
+        notify: extern "C" fn(apArg: *mut *mut c_void, nArg: c_int),
+        notify_arg: *mut c_void
+    ) -> ResultCode;
+
+    pub fn sqlite3_errmsg(db: *mut dbh) -> *const c_char;
+    pub fn sqlite3_prepare_v2(db: *mut dbh, sql: *const c_char, n: c_int, stmt: *mut *mut stmt, tail: *mut *const c_char) -> ResultCode;
+
+    pub fn sqlite3_blob_open(db: *mut dbh, dbname: *const c_char, table: *const c_char, column: *const c_char, rowid: i64, flags: c_int, blob: *mut *mut blob) -> ResultCode;
+    pub fn sqlite3_blob_read(blob: *mut blob, buf: *mut c_void, n: c_int, offset: c_int) -> ResultCode;
+    pub fn sqlite3_blob_write(blob: *mut blob, buf: *const c_void, n: c_int, offset: c_int) -> ResultCode;
+    pub fn sqlite3_blob_bytes(blob: *mut blob) -> c_int;
+    pub fn sqlite3_blob_close(blob: *mut blob) -> ResultCode;
+
+    pub fn sqlite3_create_function_v2(
+        db: *mut dbh, name: *const c_char, n_args: c_int, encoding: c_int, app: *mut c_void,
+        x_func: Option<extern "C" fn(ctx: *mut sqlite3_context, argc: c_int, argv: *mut *mut sqlite3_value)>,
+        x_step: Option<extern "C" fn(ctx: *mut sqlite3_context, argc: c_int, argv: *mut *mut sqlite3_value)>,
+        x_final: Option<extern "C" fn(ctx: *mut sqlite3_context)>,
+        x_destroy: extern "C" fn(app: *mut c_void)
+    ) -> ResultCode;
+
+    pub fn sqlite3_value_type(value: *mut sqlite3_value) -> c_int;
+    pub fn sqlite3_value_int(value: *mut sqlite3_value) -> c_int;
+    pub fn sqlite3_value_int64(value: *mut sqlite3_value) -> i64;
+    pub fn sqlite3_value_double(value: *mut sqlite3_value) -> c_double;
+    pub fn sqlite3_value_text(value: *mut sqlite3_value) -> *const c_char;
+    pub fn sqlite3_value_blob(value: *mut sqlite3_value) -> *const c_void;
+    pub fn sqlite3_value_bytes(value: *mut sqlite3_value) -> c_int;
+
+    pub fn sqlite3_result_int(ctx: *mut sqlite3_context, value: c_int);
+    pub fn sqlite3_result_int64(ctx: *mut sqlite3_context, value: i64);
+    pub fn sqlite3_result_double(ctx: *mut sqlite3_context, value: c_double);
+    pub fn sqlite3_result_text(ctx: *mut sqlite3_context, value: *const c_char, n: c_int, destructor: *mut c_void);
+    pub fn sqlite3_result_blob(ctx: *mut sqlite3_context, value: *const c_void, n: c_int, destructor: *mut c_void);
+    pub fn sqlite3_result_null(ctx: *mut sqlite3_context);
+    pub fn sqlite3_result_error(ctx: *mut sqlite3_context, msg: *const c_char, n: c_int);
+
+    pub fn sqlite3_user_data(ctx: *mut sqlite3_context) -> *mut c_void;
+    pub fn sqlite3_aggregate_context(ctx: *mut sqlite3_context, n_bytes: c_int) -> *mut c_void;
+}
+
+// --- Virtual table support (sqlite3_module / sqlite3_vtab / sqlite3_index_info) ---
+// See http://www.sqlite.org/c3ref/module.html and http://www.sqlite.org/vtab.html
+
+/// Mirrors `sqlite3_vtab`. The `vtab` module's `VTabHandle<T>` places this as
+/// its first field so a `*mut sqlite3_vtab` and a `*mut VTabHandle<T>` are
+/// interchangeable, the same trick SQLite itself expects implementors to use.
+#[repr(C)]
+pub struct sqlite3_vtab {
+    pub p_module: *const sqlite3_module,
+    pub n_ref: c_int,
+    pub z_err_msg: *mut c_char,
+}
+
+/// Mirrors `sqlite3_vtab_cursor`, analogous to `sqlite3_vtab` above.
+#[repr(C)]
+pub struct sqlite3_vtab_cursor {
+    pub p_vtab: *mut sqlite3_vtab,
+}
+
+#[repr(C)]
+pub struct sqlite3_index_constraint {
+    pub i_column: c_int,
+    pub op: c_uchar,
+    pub usable: c_uchar,
+    pub i_term_offset: c_int,
+}
+
+#[repr(C)]
+pub struct sqlite3_index_constraint_usage {
+    pub argv_index: c_int,
+    pub omit: c_uchar,
+}
+
+#[repr(C)]
+pub struct sqlite3_index_orderby {
+    pub i_column: c_int,
+    pub desc: c_uchar,
+}
+
+#[repr(C)]
+pub struct sqlite3_index_info {
+    pub n_constraint: c_int,
+    pub a_constraint: *const sqlite3_index_constraint,
+    pub n_order_by: c_int,
+    pub a_order_by: *const sqlite3_index_orderby,
+    pub a_constraint_usage: *mut sqlite3_index_constraint_usage,
+    pub idx_num: c_int,
+    pub idx_str: *mut c_char,
+    pub need_to_free_idx_str: c_int,
+    pub order_by_consumed: c_int,
+    pub estimated_cost: c_double,
+    pub estimated_rows: i64,
+}
+
+#[repr(C)]
+pub struct sqlite3_module {
+    pub i_version: c_int,
+    pub x_create: extern "C" fn(db: *mut dbh, p_aux: *mut c_void, argc: c_int, argv: *const *const c_char, pp_vtab: *mut *mut sqlite3_vtab, pz_err: *mut *mut c_char) -> c_int,
+    pub x_connect: extern "C" fn(db: *mut dbh, p_aux: *mut c_void, argc: c_int, argv: *const *const c_char, pp_vtab: *mut *mut sqlite3_vtab, pz_err: *mut *mut c_char) -> c_int,
+    pub x_best_index: extern "C" fn(p_vtab: *mut sqlite3_vtab, info: *mut sqlite3_index_info) -> c_int,
+    pub x_disconnect: extern "C" fn(p_vtab: *mut sqlite3_vtab) -> c_int,
+    pub x_destroy: extern "C" fn(p_vtab: *mut sqlite3_vtab) -> c_int,
+    pub x_open: extern "C" fn(p_vtab: *mut sqlite3_vtab, pp_cursor: *mut *mut sqlite3_vtab_cursor) -> c_int,
+    pub x_close: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor) -> c_int,
+    pub x_filter: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor, idx_num: c_int, idx_str: *const c_char, argc: c_int, argv: *mut *mut sqlite3_value) -> c_int,
+    pub x_next: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor) -> c_int,
+    pub x_eof: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor) -> c_int,
+    pub x_column: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor, ctx: *mut sqlite3_context, i: c_int) -> c_int,
+    pub x_rowid: extern "C" fn(p_cursor: *mut sqlite3_vtab_cursor, p_rowid: *mut i64) -> c_int,
+    pub x_update: *const c_void, // not supported: read-only virtual tables only
+    pub x_begin: *const c_void,
+    pub x_sync: *const c_void,
+    pub x_commit: *const c_void,
+    pub x_rollback: *const c_void,
+    pub x_find_function: *const c_void,
+    pub x_rename: *const c_void,
+}
+
+extern "C" {
+    pub fn sqlite3_create_module_v2(
+        db: *mut dbh, name: *const c_char, module: *const sqlite3_module,
+        app: *mut c_void, x_destroy: extern "C" fn(app: *mut c_void)
+    ) -> ResultCode;
+
+    pub fn sqlite3_declare_vtab(db: *mut dbh, sql: *const c_char) -> ResultCode;
+}
@@ -0,0 +1,204 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+//! Registration of Rust closures as application-defined SQL scalar and
+//! aggregate functions, on top of `sqlite3_create_function_v2`.
+
+use ffi::*;
+use libc::{c_int, c_char, c_void};
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::str;
+use types::*;
+
+/// Reads the `sqlite3_value` at argument index `i` out of `argv` and
+/// converts it to a `BindArg`, reusing the same storage-class dispatch
+/// `Cursor.step_row()` uses for result columns.
+pub unsafe fn arg_to_bindarg(argv: *mut *mut sqlite3_value, i: int) -> BindArg<'static> {
+    let v = *argv.offset(i);
+    match column_type_from_raw(sqlite3_value_type(v) as int) {
+        SQLITE_INTEGER => Integer(sqlite3_value_int(v) as int),
+        SQLITE_FLOAT   => Float64(sqlite3_value_double(v)),
+        SQLITE_TEXT    => {
+            let ptr = sqlite3_value_text(v);
+            let len = sqlite3_value_bytes(v) as uint;
+            slice::raw::buf_as_slice(ptr as *const u8, len, |bytes| {
+                Text(str::from_utf8(bytes).unwrap().to_string())
+            })
+        }
+        SQLITE_BLOB    => {
+            let ptr = sqlite3_value_blob(v);
+            let len = sqlite3_value_bytes(v) as uint;
+            slice::raw::buf_as_slice(ptr as *const u8, len, |bytes| Blob(bytes.to_vec()))
+        }
+        SQLITE_NULL    => Null,
+    }
+}
+
+/// Pushes a `BindArg` back into SQLite as the result of a scalar/aggregate
+/// function call, via `sqlite3_result_*`.
+pub unsafe fn set_result(ctx: *mut sqlite3_context, result: BindArg<'static>) {
+    match result {
+        Text(v) => v.with_c_str(|_v| sqlite3_result_text(ctx, _v, v.len() as c_int, -1 as *mut c_void)),
+        StaticText(v) => sqlite3_result_text(ctx, v.as_ptr() as *const c_char, v.len() as c_int, 0 as *mut c_void),
+        TextBorrowed(v) => sqlite3_result_text(ctx, v.as_ptr() as *const c_char, v.len() as c_int, 0 as *mut c_void),
+        Blob(v) => sqlite3_result_blob(ctx, v.as_ptr() as *const c_void, v.len() as c_int, -1 as *mut c_void),
+        BlobBorrowed(v) => sqlite3_result_blob(ctx, v.as_ptr() as *const c_void, v.len() as c_int, 0 as *mut c_void),
+        Integer(v) => sqlite3_result_int(ctx, v as c_int),
+        Integer64(v) => sqlite3_result_int64(ctx, v),
+        Float64(v) => sqlite3_result_double(ctx, v),
+        Null => sqlite3_result_null(ctx),
+    }
+}
+
+/// The boxed state behind the `app` pointer passed to
+/// `sqlite3_create_function_v2` for a scalar function.
+struct ScalarFunction {
+    f: Box<FnMut(&[BindArg<'static>]) -> BindArg<'static> + 'static>,
+}
+
+extern "C" fn scalar_func(ctx: *mut sqlite3_context, argc: c_int, argv: *mut *mut sqlite3_value) {
+    unsafe {
+        let state: &mut ScalarFunction = mem::transmute(sqlite3_user_data(ctx));
+        let mut args = Vec::with_capacity(argc as uint);
+        let mut i = 0i;
+        while i < argc as int {
+            args.push(arg_to_bindarg(argv, i));
+            i += 1;
+        }
+        let result = (state.f)(args.as_slice());
+        set_result(ctx, result);
+    }
+}
+
+extern "C" fn destroy_scalar_function(app: *mut c_void) {
+    unsafe {
+        let _: Box<ScalarFunction> = mem::transmute(app);
+        // dropped here, freeing the boxed closure
+    }
+}
+
+/// Registers `f` as a scalar SQL function named `name` taking `n_args`
+/// arguments (or a variable number, if `n_args` is negative).
+/// See http://www.sqlite.org/c3ref/create_function.html
+pub fn create_scalar_function(dbh: *mut dbh, name: &str, n_args: int,
+                              f: Box<FnMut(&[BindArg<'static>]) -> BindArg<'static> + 'static>) -> ResultCode {
+    let state = box ScalarFunction { f: f };
+    let app = unsafe { mem::transmute(state) };
+    name.with_c_str(|_name| unsafe {
+        // A scalar function gets only `xFunc`; passing a non-null
+        // `xStep`/`xFinal` alongside it is undefined behavior per
+        // `sqlite3_create_function_v2`'s documented contract.
+        sqlite3_create_function_v2(dbh, _name, n_args as c_int, SQLITE_UTF8, app,
+                                    Some(scalar_func), None, None,
+                                    destroy_scalar_function)
+    })
+}
+
+/// The boxed state behind the `app` pointer for an aggregate function: the
+/// per-row step closure, the finalize closure, and a factory for a fresh
+/// accumulator, since `sqlite3_aggregate_context` only guarantees zeroed
+/// memory, not a well-formed Rust value.
+struct AggregateFunction<A> {
+    init: Box<Fn() -> A + 'static>,
+    step: Box<FnMut(&mut A, &[BindArg<'static>]) + 'static>,
+    finalize: Box<FnMut(A) -> BindArg<'static> + 'static>,
+}
+
+/// The block `sqlite3_aggregate_context` hands back, sized for this
+/// accumulator type. SQLite guarantees the block is zeroed on first
+/// allocation, which is a well-defined `false` for `initialized` but says
+/// nothing about `acc` being a legal `A` -- unlike `Option<A>`'s
+/// discriminant, a `bool`'s only valid bit patterns are 0 and 1, so reading
+/// `initialized` before `acc` is ever written is not undefined behavior.
+struct AggregateSlot<A> {
+    initialized: bool,
+    acc: A,
+}
+
+unsafe fn aggregate_slot<A>(ctx: *mut sqlite3_context, state: &AggregateFunction<A>) -> *mut AggregateSlot<A> {
+    let slot = sqlite3_aggregate_context(ctx, mem::size_of::<AggregateSlot<A>>() as c_int) as *mut AggregateSlot<A>;
+    if !(*slot).initialized {
+        ptr::write(&mut (*slot).acc, (state.init)());
+        (*slot).initialized = true;
+    }
+    slot
+}
+
+extern "C" fn aggregate_step<A>(ctx: *mut sqlite3_context, argc: c_int, argv: *mut *mut sqlite3_value) {
+    unsafe {
+        let state: &mut AggregateFunction<A> = mem::transmute(sqlite3_user_data(ctx));
+        let slot = aggregate_slot(ctx, state);
+        let mut args = Vec::with_capacity(argc as uint);
+        let mut i = 0i;
+        while i < argc as int {
+            args.push(arg_to_bindarg(argv, i));
+            i += 1;
+        }
+        (state.step)(&mut (*slot).acc, args.as_slice());
+    }
+}
+
+extern "C" fn aggregate_final<A>(ctx: *mut sqlite3_context) {
+    unsafe {
+        let state: &mut AggregateFunction<A> = mem::transmute(sqlite3_user_data(ctx));
+        let slot = aggregate_slot(ctx, state);
+        let acc = ptr::read(&(*slot).acc); // move out; SQLite reclaims the block itself
+        let result = (state.finalize)(acc);
+        set_result(ctx, result);
+    }
+}
+
+extern "C" fn destroy_aggregate_function<A>(app: *mut c_void) {
+    unsafe {
+        let _: Box<AggregateFunction<A>> = mem::transmute(app);
+    }
+}
+
+/// Registers an aggregate SQL function named `name` taking `n_args`
+/// arguments, backed by an `init`/`step`/`finalize` triple and an
+/// accumulator of type `A` stored via `sqlite3_aggregate_context`.
+pub fn create_aggregate_function<A>(dbh: *mut dbh, name: &str, n_args: int,
+                                    init: Box<Fn() -> A + 'static>,
+                                    step: Box<FnMut(&mut A, &[BindArg<'static>]) + 'static>,
+                                    finalize: Box<FnMut(A) -> BindArg<'static> + 'static>) -> ResultCode {
+    let state = box AggregateFunction { init: init, step: step, finalize: finalize };
+    let app = unsafe { mem::transmute(state) };
+    name.with_c_str(|_name| unsafe {
+        // An aggregate gets only `xStep`/`xFinal`; passing a non-null
+        // `xFunc` alongside them is undefined behavior per
+        // `sqlite3_create_function_v2`'s documented contract.
+        sqlite3_create_function_v2(dbh, _name, n_args as c_int, SQLITE_UTF8, app,
+                                    None, Some(aggregate_step::<A>), Some(aggregate_final::<A>),
+                                    destroy_aggregate_function::<A>)
+    })
+}
@@ -0,0 +1,109 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+use ffi::ResultCode;
+use std::collections::HashMap;
+
+/// A value bound into a prepared statement with `Cursor.bind_param()`, and
+/// also the value type used to represent a result column in a `RowMap`.
+///
+/// `TextBorrowed`/`BlobBorrowed` carry a `&'stmt` slice instead of an owned
+/// `String`/`Vec<u8>`; binding one passes `SQLITE_STATIC` instead of making
+/// SQLite copy the value, so `'stmt` must last until the parameter is
+/// rebound, `clear_bindings` is called or the statement is finalized, same as
+/// `StaticText`; `reset` and `step` keep the binding in place.
+pub enum BindArg<'stmt> {
+    Text(String),
+    StaticText(&'static str),
+    TextBorrowed(&'stmt str),
+    Blob(Vec<u8>),
+    BlobBorrowed(&'stmt [u8]),
+    Integer(int),
+    Integer64(i64),
+    Float64(f64),
+    Null,
+}
+
+pub use self::BindArg::{Text, StaticText, TextBorrowed, Blob, BlobBorrowed, Integer, Integer64, Float64, Null};
+
+/// The storage class SQLite reports for a result column.
+/// See http://www.sqlite.org/c3ref/c_blob.html
+#[deriving(PartialEq, Show)]
+pub enum ColumnType {
+    SQLITE_INTEGER,
+    SQLITE_FLOAT,
+    SQLITE_TEXT,
+    SQLITE_BLOB,
+    SQLITE_NULL,
+}
+
+pub use self::ColumnType::{SQLITE_INTEGER, SQLITE_FLOAT, SQLITE_TEXT, SQLITE_BLOB, SQLITE_NULL};
+
+/// Maps a raw SQLite storage-class constant (as returned by
+/// `sqlite3_column_type` or `sqlite3_value_type`) to a `ColumnType`. Shared
+/// by `Cursor.get_column_type()` and the function-argument dispatch in
+/// `function::arg_to_bindarg()` so both agree on what each integer means.
+pub fn column_type_from_raw(ct: int) -> ColumnType {
+    match ct {
+        1 /* SQLITE_INTEGER */ => SQLITE_INTEGER,
+        2 /* SQLITE_FLOAT   */ => SQLITE_FLOAT,
+        3 /* SQLITE_TEXT    */ => SQLITE_TEXT,
+        4 /* SQLITE_BLOB    */ => SQLITE_BLOB,
+        5 /* SQLITE_NULL    */ => SQLITE_NULL,
+        _ => panic!(format!("sqlite internal error: Got an unknown column type ({:d}) back from the library.", ct)),
+    }
+}
+
+/// A single result row, keyed by column name. Result columns are always
+/// materialized into owned values, so a `RowMap` never borrows.
+pub type RowMap = HashMap<String, BindArg<'static>>;
+
+pub type SqliteResult<T> = Result<T, ResultCode>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn column_type_from_raw_maps_each_storage_class() {
+        assert_eq!(column_type_from_raw(1), SQLITE_INTEGER);
+        assert_eq!(column_type_from_raw(2), SQLITE_FLOAT);
+        assert_eq!(column_type_from_raw(3), SQLITE_TEXT);
+        assert_eq!(column_type_from_raw(4), SQLITE_BLOB);
+        assert_eq!(column_type_from_raw(5), SQLITE_NULL);
+    }
+
+    #[test]
+    #[should_fail]
+    fn column_type_from_raw_panics_on_unknown_code() {
+        column_type_from_raw(0);
+    }
+}
@@ -0,0 +1,402 @@
+/*
+** Copyright (c) 2011, Brian Smith <brian@linuxfood.net>
+** All rights reserved.
+**
+** Redistribution and use in source and binary forms, with or without
+** modification, are permitted provided that the following conditions are met:
+**
+**   * Redistributions of source code must retain the above copyright notice,
+**     this list of conditions and the following disclaimer.
+**
+**   * Redistributions in binary form must reproduce the above copyright notice,
+**     this list of conditions and the following disclaimer in the documentation
+**     and/or other materials provided with the distribution.
+**
+**   * Neither the name of Brian Smith nor the names of its contributors
+**     may be used to endorse or promote products derived from this software
+**     without specific prior written permission.
+**
+** THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+** AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+** IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+** ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+** LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+** CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+** SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+** INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+** CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+** ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+** POSSIBILITY OF SUCH DAMAGE.
+*/
+
+//! Application-defined virtual tables, on top of `sqlite3_create_module_v2`.
+//!
+//! A module implements `VTab` (schema declaration and query planning) and
+//! `VTab::Cursor` implements `VTabCursor` (row iteration). Both the
+//! `sqlite3_vtab` and `sqlite3_vtab_cursor` SQLite hands back to us are
+//! always really a `VTabHandle<T>`/`VTabCursorHandle<C>` with the C struct
+//! as its first field, so a pointer to one is a pointer to the other.
+
+use ffi::*;
+use libc::{c_int, c_char, c_void};
+use std::c_str::CString;
+use std::mem;
+use std::ptr;
+use std::str;
+use function::{arg_to_bindarg, set_result};
+use types::*;
+
+/// Row iteration over an open virtual table cursor.
+pub trait VTabCursor {
+    /// Begins a scan using the plan `best_index` selected -- `idx_num` and
+    /// `idx_str` are exactly the values that call passed to
+    /// `IndexInfo::set_idx_num`/left as `idxStr`, so a vtab with more than
+    /// one query plan can tell which one it is being asked to run -- with
+    /// the constraint arguments `best_index` marked as used, in argument
+    /// order.
+    /// See http://www.sqlite.org/vtab.html#xfilter
+    fn filter(&mut self, idx_num: int, idx_str: Option<&str>, args: &[BindArg<'static>]) -> ResultCode;
+
+    /// Advances to the next row.
+    /// See http://www.sqlite.org/vtab.html#xnext
+    fn next(&mut self) -> ResultCode;
+
+    /// Returns `true` once the scan has moved past the last row.
+    fn eof(&self) -> bool;
+
+    /// Returns the value of column `i` (0-based) at the current row.
+    fn column(&self, i: int) -> BindArg<'static>;
+
+    /// Returns the rowid of the current row.
+    fn rowid(&self) -> i64;
+}
+
+/// An application-defined virtual table module.
+pub trait VTab {
+    type Cursor: VTabCursor;
+
+    /// Connects to an existing instance of this virtual table, returning
+    /// the `CREATE TABLE`-style schema to pass to `sqlite3_declare_vtab`
+    /// along with the table's state.
+    /// See http://www.sqlite.org/vtab.html#xconnect
+    fn connect(dbh: *mut dbh, args: &[String]) -> SqliteResult<(String, Self)>;
+
+    /// Creates a new instance of this virtual table. Defaults to `connect`,
+    /// which is correct for tables with no persistent backing store of
+    /// their own (the common case for an in-memory or computed source).
+    /// See http://www.sqlite.org/vtab.html#xcreate
+    fn create(dbh: *mut dbh, args: &[String]) -> SqliteResult<(String, Self)> {
+        VTab::connect(dbh, args)
+    }
+
+    /// Fills in usage/cost estimates on `info` for one candidate query plan.
+    /// See http://www.sqlite.org/vtab.html#xbestindex
+    fn best_index(&self, info: &mut IndexInfo);
+
+    /// Opens a new cursor over this table.
+    /// See http://www.sqlite.org/vtab.html#xopen
+    fn open(&self) -> SqliteResult<Self::Cursor>;
+}
+
+/// A borrowed view of the `sqlite3_index_info` SQLite passes to
+/// `xBestIndex`, letting `VTab::best_index` inspect usable constraints and
+/// report back which ones it will consume and at what estimated cost.
+pub struct IndexInfo<'a> {
+    raw: &'a mut sqlite3_index_info,
+}
+
+impl<'a> IndexInfo<'a> {
+
+    /// The number of candidate constraints.
+    pub fn num_constraints(&self) -> int {
+        self.raw.n_constraint as int
+    }
+
+    /// The column the `i`th constraint applies to.
+    pub fn constraint_column(&self, i: int) -> int {
+        unsafe { (*self.raw.a_constraint.offset(i)).i_column as int }
+    }
+
+    /// Whether the `i`th constraint can actually be used (some constraints
+    /// are reported but are not usable in the current context).
+    pub fn constraint_usable(&self, i: int) -> bool {
+        unsafe { (*self.raw.a_constraint.offset(i)).usable != 0 }
+    }
+
+    /// Marks the `i`th constraint as consumed by this plan: it will be
+    /// passed to `VTabCursor.filter()` as argument `argv_index` (1-based),
+    /// and if `omit` is set SQLite will not double-check it itself.
+    pub fn set_constraint_usage(&mut self, i: int, argv_index: int, omit: bool) {
+        unsafe {
+            let usage = self.raw.a_constraint_usage.offset(i);
+            (*usage).argv_index = argv_index as c_int;
+            (*usage).omit = omit as c_int as u8;
+        }
+    }
+
+    /// Sets `idxNum`, an opaque plan identifier passed back to `filter()`.
+    pub fn set_idx_num(&mut self, n: int) {
+        self.raw.idx_num = n as c_int;
+    }
+
+    /// Sets the estimated cost of this plan, used by SQLite's query planner
+    /// to choose between plans when more than one table offers one.
+    pub fn set_estimated_cost(&mut self, cost: f64) {
+        self.raw.estimated_cost = cost;
+    }
+
+    /// Sets the estimated number of rows this plan will produce.
+    pub fn set_estimated_rows(&mut self, rows: i64) {
+        self.raw.estimated_rows = rows;
+    }
+}
+
+/// What `*mut sqlite3_vtab` actually points to: the required C header
+/// followed by the implementation's own state.
+#[repr(C)]
+struct VTabHandle<T> {
+    base: sqlite3_vtab,
+    imp: T,
+}
+
+/// What `*mut sqlite3_vtab_cursor` actually points to, analogous to
+/// `VTabHandle<T>` above.
+#[repr(C)]
+struct VTabCursorHandle<C> {
+    base: sqlite3_vtab_cursor,
+    imp: C,
+}
+
+unsafe fn parse_args(argc: c_int, argv: *const *const c_char) -> Vec<String> {
+    let mut args = Vec::with_capacity(argc as uint);
+    let mut i = 0i;
+    while i < argc as int {
+        let s = CString::new(*argv.offset(i), false);
+        args.push(str::from_utf8(s.as_bytes_no_nul()).unwrap().to_string());
+        i += 1;
+    }
+    args
+}
+
+unsafe fn connect_or_create<T: VTab>(db: *mut dbh, argc: c_int, argv: *const *const c_char,
+                                     pp_vtab: *mut *mut sqlite3_vtab, pz_err: *mut *mut c_char,
+                                     is_create: bool) -> c_int {
+    let args = parse_args(argc, argv);
+    let result = if is_create { T::create(db, args.as_slice()) } else { T::connect(db, args.as_slice()) };
+    match result {
+        Ok((schema, imp)) => {
+            let r = schema.with_c_str(|_schema| sqlite3_declare_vtab(db, _schema));
+            if r != SQLITE_OK {
+                return r;
+            }
+            let handle = box VTabHandle {
+                base: sqlite3_vtab { p_module: ptr::null(), n_ref: 0, z_err_msg: ptr::null_mut() },
+                imp: imp,
+            };
+            *pp_vtab = mem::transmute(handle);
+            SQLITE_OK
+        }
+        Err(r) => {
+            *pz_err = ptr::null_mut(); // the error code alone is enough context here
+            r
+        }
+    }
+}
+
+extern "C" fn x_create<T: VTab>(db: *mut dbh, _p_aux: *mut c_void, argc: c_int, argv: *const *const c_char,
+                                pp_vtab: *mut *mut sqlite3_vtab, pz_err: *mut *mut c_char) -> c_int {
+    unsafe { connect_or_create::<T>(db, argc, argv, pp_vtab, pz_err, true) }
+}
+
+extern "C" fn x_connect<T: VTab>(db: *mut dbh, _p_aux: *mut c_void, argc: c_int, argv: *const *const c_char,
+                                 pp_vtab: *mut *mut sqlite3_vtab, pz_err: *mut *mut c_char) -> c_int {
+    unsafe { connect_or_create::<T>(db, argc, argv, pp_vtab, pz_err, false) }
+}
+
+extern "C" fn x_best_index<T: VTab>(p_vtab: *mut sqlite3_vtab, info: *mut sqlite3_index_info) -> c_int {
+    unsafe {
+        let handle: &VTabHandle<T> = mem::transmute(p_vtab);
+        let mut wrapped = IndexInfo { raw: mem::transmute(info) };
+        handle.imp.best_index(&mut wrapped);
+    }
+    SQLITE_OK
+}
+
+extern "C" fn x_disconnect<T: VTab>(p_vtab: *mut sqlite3_vtab) -> c_int {
+    unsafe {
+        let _: Box<VTabHandle<T>> = mem::transmute(p_vtab);
+        // dropped here, freeing the boxed implementation
+    }
+    SQLITE_OK
+}
+
+extern "C" fn x_open<T: VTab>(p_vtab: *mut sqlite3_vtab, pp_cursor: *mut *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        let handle: &VTabHandle<T> = mem::transmute(p_vtab);
+        match handle.imp.open() {
+            Ok(cursor) => {
+                let boxed = box VTabCursorHandle {
+                    base: sqlite3_vtab_cursor { p_vtab: p_vtab },
+                    imp: cursor,
+                };
+                *pp_cursor = mem::transmute(boxed);
+                SQLITE_OK
+            }
+            Err(r) => r,
+        }
+    }
+}
+
+extern "C" fn x_close<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        let _: Box<VTabCursorHandle<C>> = mem::transmute(p_cursor);
+    }
+    SQLITE_OK
+}
+
+extern "C" fn x_filter<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor, idx_num: c_int,
+                                      idx_str: *const c_char, argc: c_int, argv: *mut *mut sqlite3_value) -> c_int {
+    unsafe {
+        let handle: &mut VTabCursorHandle<C> = mem::transmute(p_cursor);
+        let mut args = Vec::with_capacity(argc as uint);
+        let mut i = 0i;
+        while i < argc as int {
+            args.push(arg_to_bindarg(argv, i));
+            i += 1;
+        }
+        let idx_str = if idx_str.is_null() {
+            None
+        } else {
+            let s = CString::new(idx_str, false);
+            Some(str::from_utf8(s.as_bytes_no_nul()).unwrap().to_string())
+        };
+        handle.imp.filter(idx_num as int, idx_str.as_ref().map(|s| s.as_slice()), args.as_slice())
+    }
+}
+
+extern "C" fn x_next<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        let handle: &mut VTabCursorHandle<C> = mem::transmute(p_cursor);
+        handle.imp.next()
+    }
+}
+
+extern "C" fn x_eof<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        let handle: &VTabCursorHandle<C> = mem::transmute(p_cursor);
+        handle.imp.eof() as c_int
+    }
+}
+
+extern "C" fn x_column<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor, ctx: *mut sqlite3_context, i: c_int) -> c_int {
+    unsafe {
+        let handle: &VTabCursorHandle<C> = mem::transmute(p_cursor);
+        set_result(ctx, handle.imp.column(i as int));
+    }
+    SQLITE_OK
+}
+
+extern "C" fn x_rowid<C: VTabCursor>(p_cursor: *mut sqlite3_vtab_cursor, p_rowid: *mut i64) -> c_int {
+    unsafe {
+        let handle: &VTabCursorHandle<C> = mem::transmute(p_cursor);
+        *p_rowid = handle.imp.rowid();
+    }
+    SQLITE_OK
+}
+
+extern "C" fn destroy_module_app(_app: *mut c_void) {}
+
+/// Registers `T` as a virtual table module named `name`, usable in `CREATE
+/// VIRTUAL TABLE ... USING name(...)` statements on this connection. The
+/// `sqlite3_module` itself is intentionally leaked -- like the rest of
+/// SQLite's registration callbacks, it must stay valid for the life of the
+/// connection, not just this call.
+/// See http://www.sqlite.org/c3ref/create_module.html
+pub fn create_module<T: VTab>(dbh: *mut dbh, name: &str) -> ResultCode {
+    let module = box sqlite3_module {
+        i_version: 1,
+        x_create: x_create::<T>,
+        x_connect: x_connect::<T>,
+        x_best_index: x_best_index::<T>,
+        x_disconnect: x_disconnect::<T>,
+        x_destroy: x_disconnect::<T>, // no persistent storage to drop beyond our own state
+        x_open: x_open::<T>,
+        x_close: x_close::<T::Cursor>,
+        x_filter: x_filter::<T::Cursor>,
+        x_next: x_next::<T::Cursor>,
+        x_eof: x_eof::<T::Cursor>,
+        x_column: x_column::<T::Cursor>,
+        x_rowid: x_rowid::<T::Cursor>,
+        x_update: ptr::null(),
+        x_begin: ptr::null(),
+        x_sync: ptr::null(),
+        x_commit: ptr::null(),
+        x_rollback: ptr::null(),
+        x_find_function: ptr::null(),
+        x_rename: ptr::null(),
+    };
+    name.with_c_str(|_name| unsafe {
+        sqlite3_create_module_v2(dbh, _name, mem::transmute(module), 0 as *mut c_void, destroy_module_app)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::IndexInfo;
+    use ffi::{sqlite3_index_info, sqlite3_index_constraint, sqlite3_index_constraint_usage};
+
+    /// Builds a hand-rolled `sqlite3_index_info` with one candidate
+    /// constraint, exercising `IndexInfo`'s getters/setters without needing
+    /// a real SQLite connection to hand one to us.
+    fn with_one_constraint(f: |&mut IndexInfo|) {
+        let mut constraint = sqlite3_index_constraint {
+            i_column: 2,
+            op: 0,
+            usable: 1,
+            i_term_offset: 0,
+        };
+        let mut usage = sqlite3_index_constraint_usage { argv_index: 0, omit: 0 };
+        let mut raw = sqlite3_index_info {
+            n_constraint: 1,
+            a_constraint: &mut constraint as *mut _ as *const _,
+            n_order_by: 0,
+            a_order_by: 0 as *const _,
+            a_constraint_usage: &mut usage as *mut _,
+            idx_num: 0,
+            idx_str: 0 as *mut _,
+            need_to_free_idx_str: 0,
+            order_by_consumed: 0,
+            estimated_cost: 0.0,
+            estimated_rows: 0,
+        };
+        let mut info = IndexInfo { raw: &mut raw };
+        f(&mut info);
+    }
+
+    #[test]
+    fn reads_constraint_column_and_usability() {
+        with_one_constraint(|info| {
+            assert_eq!(info.num_constraints(), 1i);
+            assert_eq!(info.constraint_column(0), 2i);
+            assert!(info.constraint_usable(0));
+        });
+    }
+
+    #[test]
+    fn setters_write_through_to_the_raw_struct() {
+        with_one_constraint(|info| {
+            info.set_constraint_usage(0, 1, true);
+            info.set_idx_num(7);
+            info.set_estimated_cost(1.5);
+            info.set_estimated_rows(42);
+            assert_eq!(info.raw.a_constraint_usage.is_null(), false);
+            unsafe {
+                assert_eq!((*info.raw.a_constraint_usage).argv_index, 1);
+                assert_eq!((*info.raw.a_constraint_usage).omit, 1);
+            }
+            assert_eq!(info.raw.idx_num, 7);
+            assert_eq!(info.raw.estimated_cost, 1.5);
+            assert_eq!(info.raw.estimated_rows, 42);
+        });
+    }
+}